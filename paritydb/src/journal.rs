@@ -1,13 +1,16 @@
-use std::collections::vec_deque::Drain;
-use std::collections::{BTreeSet, HashMap, VecDeque, btree_set};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque, btree_set};
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::io::Write;
+use std::mem;
 use std::path::{PathBuf, Path};
 use std::slice;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use hex_slice::AsHex;
 use memmap::{Mmap, Protection};
+use snap::{Encoder as SnapEncoder, Decoder as SnapDecoder};
 use tiny_keccak::sha3_256;
 
 use error::{ErrorKind, Result};
@@ -15,6 +18,16 @@ use transaction::{Transaction, OperationsIterator, Operation};
 
 const CHECKSUM_SIZE: usize = 32;
 
+/// Magic bytes identifying a paritydb journal era file.
+const MAGIC: &[u8] = b"PDBJ";
+/// Current on-disk/on-wire era format version.
+const CURRENT_VERSION: u16 = 2;
+/// magic (4 bytes) + format version (2 bytes, little-endian) + flags (1 byte) +
+/// uncompressed payload length (4 bytes, little-endian)
+const HEADER_SIZE: usize = 11;
+/// `flags` bit indicating the payload has been Snappy-compressed.
+const FLAG_COMPRESSED: u8 = 0b0000_0001;
+
 #[derive(Debug, PartialEq)]
 enum JournalOperation<T> {
 	Insert(T),
@@ -67,63 +80,128 @@ unsafe fn cache_memory(memory: &[u8]) -> HashMap<JournalSlice, JournalOperation<
 	}).collect()
 }
 
+/// Backing storage for a single era: either mmap'd from disk, or an owned in-memory buffer.
+#[derive(Debug)]
+enum EraData {
+	Mapped(Mmap),
+	Owned(Vec<u8>),
+}
+
+impl EraData {
+	fn as_slice(&self) -> &[u8] {
+		match *self {
+			EraData::Mapped(ref mmap) => unsafe { mmap.as_slice() },
+			EraData::Owned(ref bytes) => bytes,
+		}
+	}
+}
+
 #[derive(Debug)]
 pub struct JournalEra {
-	file: PathBuf,
-	mmap: Mmap,
+	index: u64,
+	data: EraData,
+	/// Whether `data` holds just the decompressed payload (`true`) or the whole on-wire
+	/// buffer including header and checksum (`false`, the zero-copy mmap path).
+	compressed: bool,
 	cache: HashMap<JournalSlice, JournalOperation<JournalSlice>>,
 }
 
+// SAFETY: every `JournalSlice` pointer in `cache` only ever borrows bytes owned by this same
+// `JournalEra`'s `data` (an owned `Vec<u8>`, or an mmap'd region that outlives the struct).
+// That backing allocation's address is fixed once `cache` is built from it in `from_data` and
+// is never mutated afterwards, so moving or sharing a `JournalEra` across threads never
+// invalidates or races those pointers; only read-only slice access happens through them.
+unsafe impl Send for JournalEra {}
+unsafe impl Sync for JournalEra {}
+
 impl JournalEra {
-	// TODO [ToDr] Data should be written to a file earlier (for instance when transaction is created).
-	// Consider an API like this:
-	// ```
-	// let mut transaction = Transaction::new();
-	// ...
-	// let prepared = db.prepare(transaction); // writes to a file (doesn't require write access to DB)
-	// db.apply(prepared); // actually insert to db (requires write access)
-	// ```
-	fn create<P: AsRef<Path>>(file_path: P, transaction: &Transaction) -> Result<JournalEra> {
-		let hash = sha3_256(transaction.raw());
-		let mut file = fs::OpenOptions::new()
-			.write(true)
-			.create_new(true)
-			.open(&file_path)?;
+	/// Serializes `transaction` into the era wire format: a header (magic + format version +
+	/// flags + uncompressed length), a sha3 checksum computed over the stored bytes, and the
+	/// (optionally Snappy-compressed) transaction bytes.
+	fn encode(transaction: &Transaction, compression: Compression) -> Result<Vec<u8>> {
+		let raw = transaction.raw();
 
-		file.write_all(&hash)?;
-		file.write_all(transaction.raw())?;
-		file.flush()?;
+		let (flags, stored) = match compression {
+			Compression::Snappy => {
+				let compressed = SnapEncoder::new().compress_vec(raw)
+					.map_err(|e| ErrorKind::CorruptedJournal(PathBuf::new(), format!("snappy compression failed: {}", e)))?;
+				(FLAG_COMPRESSED, compressed)
+			},
+			Compression::None => (0, raw.to_vec()),
+		};
 
-		Self::open(file_path)
+		let hash = sha3_256(&stored);
+		let uncompressed_len = raw.len() as u32;
+
+		let mut bytes = Vec::with_capacity(HEADER_SIZE + CHECKSUM_SIZE + stored.len());
+		bytes.extend_from_slice(MAGIC);
+		bytes.push((CURRENT_VERSION & 0xff) as u8);
+		bytes.push((CURRENT_VERSION >> 8) as u8);
+		bytes.push(flags);
+		bytes.push((uncompressed_len & 0xff) as u8);
+		bytes.push(((uncompressed_len >> 8) & 0xff) as u8);
+		bytes.push(((uncompressed_len >> 16) & 0xff) as u8);
+		bytes.push(((uncompressed_len >> 24) & 0xff) as u8);
+		bytes.extend_from_slice(&hash);
+		bytes.extend_from_slice(&stored);
+		Ok(bytes)
 	}
 
-	fn open<P: AsRef<Path>>(file: P) -> Result<JournalEra> {
-		let mmap = Mmap::open_path(&file, Protection::Read)?;
-		let cache = {
-			let checksum = unsafe { &mmap.as_slice()[..CHECKSUM_SIZE] };
-			let data = unsafe { &mmap.as_slice()[CHECKSUM_SIZE..] };
-			let hash = sha3_256(data);
-			if hash != checksum {
+	/// Validates the header and checksum of `data`, decompresses the payload if needed, and
+	/// indexes the operations it contains. `label` is used purely for error reporting, since
+	/// backends may not have a real file path. Eras on an older format version must already
+	/// have been upgraded by the [`migration`] module before reaching this point.
+	fn from_data(index: u64, data: EraData, label: PathBuf) -> Result<JournalEra> {
+		let bytes = data.as_slice();
+		if bytes.len() < HEADER_SIZE + CHECKSUM_SIZE || &bytes[..MAGIC.len()] != MAGIC {
+			return Err(ErrorKind::CorruptedJournal(label, "missing or invalid era header".into()).into());
+		}
+
+		let version = (bytes[MAGIC.len()] as u16) | ((bytes[MAGIC.len() + 1] as u16) << 8);
+		if version != CURRENT_VERSION {
+			return Err(ErrorKind::CorruptedJournal(
+				label,
+				format!("Unsupported era format version {} (expected {})", version, CURRENT_VERSION),
+			).into());
+		}
+
+		let flags = bytes[MAGIC.len() + 2];
+		let len_offset = MAGIC.len() + 3;
+		let uncompressed_len = (bytes[len_offset] as u32)
+			| ((bytes[len_offset + 1] as u32) << 8)
+			| ((bytes[len_offset + 2] as u32) << 16)
+			| ((bytes[len_offset + 3] as u32) << 24);
+
+		let checksum = &bytes[HEADER_SIZE..HEADER_SIZE + CHECKSUM_SIZE];
+		let stored = &bytes[HEADER_SIZE + CHECKSUM_SIZE..];
+		let hash = sha3_256(stored);
+		if hash != checksum {
+			return Err(ErrorKind::CorruptedJournal(
+				label,
+				format!(
+					"Expected: {:02x}, Got: {:02x}",
+					hash.as_hex(),
+					checksum.as_hex(),
+				)
+			).into());
+		}
+
+		if flags & FLAG_COMPRESSED != 0 {
+			let decompressed = SnapDecoder::new().decompress_vec(stored)
+				.map_err(|e| ErrorKind::CorruptedJournal(label.clone(), format!("snappy decompression failed: {}", e)))?;
+			if decompressed.len() as u32 != uncompressed_len {
 				return Err(ErrorKind::CorruptedJournal(
-					file.as_ref().into(),
-					format!(
-						"Expected: {:02x}, Got: {:02x}",
-						hash.as_hex(),
-						checksum.as_hex(),
-					)
+					label,
+					format!("decompressed length mismatch: header says {}, got {}", uncompressed_len, decompressed.len()),
 				).into());
 			}
 
-			unsafe { cache_memory(data) }
-		};
-
-		let era = JournalEra {
-			file: file.as_ref().to_path_buf(),
-			mmap,
-			cache,
-		};
-
-		Ok(era)
+			let cache = unsafe { cache_memory(&decompressed) };
+			Ok(JournalEra { index, data: EraData::Owned(decompressed), compressed: true, cache })
+		} else {
+			let cache = unsafe { cache_memory(stored) };
+			Ok(JournalEra { index, data, compressed: false, cache })
+		}
 	}
 
 	fn get<'a>(&'a self, key: &[u8]) -> Option<JournalOperation<&'a [u8]>> {
@@ -136,22 +214,81 @@ impl JournalEra {
 		}
 	}
 
+	/// Returns the era's decoded operations buffer: just the payload, decompressed if needed.
+	fn payload(&self) -> &[u8] {
+		if self.compressed {
+			self.data.as_slice()
+		} else {
+			&self.data.as_slice()[HEADER_SIZE + CHECKSUM_SIZE..]
+		}
+	}
+
 	/// Returns an iterator over era entries
 	pub fn iter(&self) -> btree_set::IntoIter<Operation> {
 		let mut set = BTreeSet::new();
 
-		for o in unsafe { OperationsIterator::new(&self.mmap.as_slice()[CHECKSUM_SIZE..]) } {
+		for o in unsafe { OperationsIterator::new(self.payload()) } {
 			set.replace(o);
 		}
 
 		set.into_iter()
 	}
 
-	/// Deletes underlying file
-	pub fn delete(self) -> Result<()> {
-		fs::remove_file(self.file)?;
-		Ok(())
+	/// Approximate heap footprint of this era: the operation cache plus the backing buffer
+	/// (mmap'd or owned).
+	pub fn mem_used(&self) -> usize {
+		self.cache_bytes() + self.mapped_bytes()
 	}
+
+	/// Approximate size of the `cache` hash map, ignoring allocator overhead. `JournalSlice`
+	/// entries are just a pointer/length pair into `data`, so this does not double-count the
+	/// backing buffer itself.
+	fn cache_bytes(&self) -> usize {
+		self.cache.len() * mem::size_of::<(JournalSlice, JournalOperation<JournalSlice>)>()
+	}
+
+	/// Size in bytes of the backing buffer: the mmap'd file, or the owned transaction buffer.
+	fn mapped_bytes(&self) -> usize {
+		self.data.as_slice().len()
+	}
+}
+
+/// Controls whether era payloads are Snappy-compressed before being written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+	/// Store the raw transaction bytes, keeping the zero-copy mmap read path.
+	None,
+	/// Compress the transaction bytes with Snappy before writing.
+	Snappy,
+}
+
+/// Persistence strategy for journal eras, decoupled from the journaling logic in [`Journal`].
+///
+/// This lets the same commit/drain machinery run over different storage strategies, e.g. the
+/// on-disk mmap'd `.era` files used in production, or an in-memory backend for tests and
+/// ephemeral DBs.
+///
+/// `Send + Sync` so a `Journal` can be shared across threads (e.g. behind an `Arc<Mutex<_>>`)
+/// and `prepare` can genuinely run concurrently with other work, rather than just type-checking
+/// as if it could.
+pub trait JournalBackend: Send + Sync + ::std::fmt::Debug {
+	/// Durably writes `transaction` as the era at `index`, without mapping it back in. Only
+	/// needs a shared reference, so `Journal::prepare` can do the expensive encode-and-write
+	/// work while holding nothing more than `&self`.
+	fn write_era(&self, index: u64, transaction: &Transaction, compression: Compression) -> Result<()>;
+	/// Persists `transaction` as a new era at `index` and returns the resulting era.
+	fn create_era(&self, index: u64, transaction: &Transaction, compression: Compression) -> Result<JournalEra> {
+		self.write_era(index, transaction, compression)?;
+		self.open_era(index)
+	}
+	/// Re-opens a previously created era at `index`.
+	fn open_era(&self, index: u64) -> Result<JournalEra>;
+	/// Returns the indices of all eras currently held by this backend, sorted ascending.
+	fn list_eras(&self) -> Result<Vec<u64>>;
+	/// Permanently removes the era at `index`. Takes `&self` (backed by interior mutability
+	/// where needed) so it can be called from [`PreparedEra`]'s `Drop` impl, which only ever
+	/// holds a shared reference to the backend.
+	fn remove_era(&self, index: u64) -> Result<()>;
 }
 
 mod dir {
@@ -161,93 +298,495 @@ mod dir {
 
 	const ERA_EXTENSION: &str = ".era";
 
-	pub fn era_files<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>> {
+	pub fn era_filename<P: AsRef<Path>>(dir: P, index: u64) -> PathBuf {
+		let mut dir = dir.as_ref().to_path_buf();
+		dir.push(format!("{}{}", index, ERA_EXTENSION));
+		dir
+	}
+
+	fn parse_index<P: AsRef<Path>>(path: P) -> Result<u64> {
+		let path = path.as_ref().display().to_string();
+		Ok(path[..path.len() - ERA_EXTENSION.len()].parse::<u64>()?)
+	}
+
+	/// Returns the era indices found in `dir`, sorted ascending, erroring if the sequence
+	/// has a gap in it.
+	pub fn era_indices<P: AsRef<Path>>(dir: P) -> Result<Vec<u64>> {
 		if !dir.as_ref().is_dir() {
 			return Err(ErrorKind::InvalidJournalLocation(dir.as_ref().into()).into());
 		}
 
-		let mut era_files: Vec<_> = read_dir(dir)?
+		let mut indices: Vec<u64> = read_dir(dir)?
 			.collect::<::std::result::Result<Vec<_>, _>>()?
 			.into_iter()
 			.filter(|entry| entry.file_name().to_string_lossy().ends_with(ERA_EXTENSION))
-			.map(|entry| entry.path())
-			.collect();
+			.map(|entry| parse_index(entry.path()))
+			.collect::<Result<Vec<_>>>()?;
 
-		era_files.sort();
+		indices.sort();
 
 		let mut last = None;
-
-		for era in &era_files {
-			let idx = era_index(era)?;
+		for &idx in &indices {
 			match last.take() {
-				Some(era) if idx == era + 1 => {},
+				Some(prev) if idx == prev + 1 => {},
 				None => {},
-				_ => {
-					return Err(ErrorKind::JournalEraMissing(idx).into());
-				}
+				_ => return Err(ErrorKind::JournalEraMissing(idx).into()),
 			}
 			last = Some(idx);
 		}
 
-		Ok(era_files)
+		Ok(indices)
 	}
+}
 
-	fn era_index<P: AsRef<Path>>(path: P) -> Result<u64> {
-		let path = path.as_ref().display().to_string();
-		Ok(1u64 + path[..path.len() - ERA_EXTENSION.len()].parse::<u64>()?)
+/// Migrates on-disk era files to [`CURRENT_VERSION`] before a journal directory is opened.
+///
+/// Old eras (pre-header: a bare checksum followed by the transaction bytes) are detected and
+/// rewritten in place. Migration steps are registered in order so that a file several
+/// versions behind is upgraded in one pass, one step at a time.
+mod migration {
+	use std::fs;
+	use std::io::{Read, Write};
+	use std::path::{Path, PathBuf};
+	use error::Result;
+	use super::{MAGIC, CURRENT_VERSION, HEADER_SIZE, CHECKSUM_SIZE};
+
+	const ERA_EXTENSION: &str = ".era";
+	/// v1 header: magic (4) + version (2) + flags (1), no uncompressed-length field yet.
+	const V1_HEADER_SIZE: usize = 7;
+
+	/// An upgrade from `from_version` to the next version.
+	struct Step {
+		from_version: u16,
+		upgrade: fn(Vec<u8>) -> Vec<u8>,
+	}
+
+	fn steps() -> Vec<Step> {
+		vec![
+			Step { from_version: 0, upgrade: upgrade_v0_to_v1 },
+			Step { from_version: 1, upgrade: upgrade_v1_to_v2 },
+		]
+	}
+
+	/// Pre-header eras are just `checksum || payload`; wrap them in a v1 header.
+	fn upgrade_v0_to_v1(bytes: Vec<u8>) -> Vec<u8> {
+		let mut upgraded = Vec::with_capacity(V1_HEADER_SIZE + bytes.len());
+		upgraded.extend_from_slice(MAGIC);
+		upgraded.push(1);
+		upgraded.push(0);
+		upgraded.push(0); // flags
+		upgraded.extend_from_slice(&bytes);
+		upgraded
+	}
+
+	/// v1 eras never compress, so the uncompressed length is simply the stored payload's
+	/// length; insert it as the new header field introduced in v2.
+	fn upgrade_v1_to_v2(bytes: Vec<u8>) -> Vec<u8> {
+		let flags = bytes[V1_HEADER_SIZE - 1];
+		let rest = &bytes[V1_HEADER_SIZE..]; // checksum || payload, unchanged by this upgrade
+		let uncompressed_len = (rest.len() - CHECKSUM_SIZE) as u32;
+
+		let mut upgraded = Vec::with_capacity(HEADER_SIZE + rest.len());
+		upgraded.extend_from_slice(MAGIC);
+		upgraded.push(2);
+		upgraded.push(0);
+		upgraded.push(flags);
+		upgraded.push((uncompressed_len & 0xff) as u8);
+		upgraded.push(((uncompressed_len >> 8) & 0xff) as u8);
+		upgraded.push(((uncompressed_len >> 16) & 0xff) as u8);
+		upgraded.push(((uncompressed_len >> 24) & 0xff) as u8);
+		upgraded.extend_from_slice(rest);
+		upgraded
 	}
 
-	pub fn next_era_index<P: AsRef<Path>>(files: &[P]) -> Result<u64> {
-		match files.last() {
-			Some(path) => era_index(path),
-			None => Ok(0),
+	fn detect_version(bytes: &[u8]) -> u16 {
+		if bytes.len() >= MAGIC.len() + 2 && &bytes[..MAGIC.len()] == MAGIC {
+			(bytes[MAGIC.len()] as u16) | ((bytes[MAGIC.len() + 1] as u16) << 8)
+		} else {
+			0
 		}
 	}
 
-	pub fn next_era_filename<P: AsRef<Path>>(dir: P, next_index: u64) -> PathBuf {
-		let mut dir = dir.as_ref().to_path_buf();
-		dir.push(format!("{}{}", next_index, ERA_EXTENSION));
-		dir
+	fn era_paths<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>> {
+		if !dir.as_ref().is_dir() {
+			return Ok(Vec::new());
+		}
+
+		Ok(fs::read_dir(dir)?
+			.collect::<::std::result::Result<Vec<_>, _>>()?
+			.into_iter()
+			.filter(|entry| entry.file_name().to_string_lossy().ends_with(ERA_EXTENSION))
+			.map(|entry| entry.path())
+			.collect())
+	}
+
+	/// Rewrites every `.era` file in `dir` that is not already on [`CURRENT_VERSION`],
+	/// chaining migration steps in order. Files already on the current version, or on an
+	/// unrecognised future version, are left untouched (the latter will be rejected by
+	/// `JournalEra::from_data` when it is opened).
+	pub fn migrate_dir<P: AsRef<Path>>(dir: P) -> Result<()> {
+		for path in era_paths(dir)? {
+			let mut bytes = Vec::new();
+			fs::File::open(&path)?.read_to_end(&mut bytes)?;
+			let original_len = bytes.len();
+			let mut version = detect_version(&bytes);
+
+			while version != CURRENT_VERSION {
+				let step = match steps().into_iter().find(|s| s.from_version == version) {
+					Some(step) => step,
+					None => break,
+				};
+				bytes = (step.upgrade)(bytes);
+				version = detect_version(&bytes);
+			}
+
+			if bytes.len() != original_len {
+				let mut file = fs::OpenOptions::new().write(true).truncate(true).open(&path)?;
+				file.write_all(&bytes)?;
+				file.flush()?;
+			}
+		}
+
+		Ok(())
 	}
 }
 
+/// The original `.era`-files-on-disk backend: each era is an mmap'd file named `{index}.era`.
 #[derive(Debug)]
-pub struct Journal {
+pub struct MmapJournalBackend {
 	dir: PathBuf,
+}
+
+impl MmapJournalBackend {
+	pub fn new<P: AsRef<Path>>(dir: P) -> MmapJournalBackend {
+		MmapJournalBackend { dir: dir.as_ref().to_path_buf() }
+	}
+}
+
+impl JournalBackend for MmapJournalBackend {
+	fn write_era(&self, index: u64, transaction: &Transaction, compression: Compression) -> Result<()> {
+		let path = dir::era_filename(&self.dir, index);
+		let bytes = JournalEra::encode(transaction, compression)?;
+
+		let mut file = fs::OpenOptions::new()
+			.write(true)
+			.create_new(true)
+			.open(&path)?;
+		file.write_all(&bytes)?;
+		file.flush()?;
+
+		Ok(())
+	}
+
+	fn open_era(&self, index: u64) -> Result<JournalEra> {
+		let path = dir::era_filename(&self.dir, index);
+		let mmap = Mmap::open_path(&path, Protection::Read)?;
+		JournalEra::from_data(index, EraData::Mapped(mmap), path)
+	}
+
+	fn list_eras(&self) -> Result<Vec<u64>> {
+		dir::era_indices(&self.dir)
+	}
+
+	fn remove_era(&self, index: u64) -> Result<()> {
+		fs::remove_file(dir::era_filename(&self.dir, index))?;
+		Ok(())
+	}
+}
+
+/// An in-memory backend useful for tests and ephemeral DBs: eras are the same encoded buffers
+/// (header, checksum and payload) that the mmap backend writes to disk, held in a `HashMap`,
+/// with nothing written to disk.
+///
+/// Wrapped in a `Mutex` (rather than a `RefCell`) so `write_era` can stage bytes through just a
+/// shared reference while keeping `InMemoryJournalBackend` itself `Sync`, as required by
+/// [`JournalBackend`]'s `Send + Sync` bound.
+#[derive(Debug, Default)]
+pub struct InMemoryJournalBackend {
+	eras: Mutex<HashMap<u64, Vec<u8>>>,
+}
+
+impl InMemoryJournalBackend {
+	pub fn new() -> InMemoryJournalBackend {
+		InMemoryJournalBackend::default()
+	}
+}
+
+impl JournalBackend for InMemoryJournalBackend {
+	fn write_era(&self, index: u64, transaction: &Transaction, compression: Compression) -> Result<()> {
+		let mut eras = self.eras.lock().unwrap();
+		if eras.contains_key(&index) {
+			let label = PathBuf::from(format!("<in-memory era {}>", index));
+			return Err(ErrorKind::CorruptedJournal(label, "era already written".into()).into());
+		}
+		let bytes = JournalEra::encode(transaction, compression)?;
+		eras.insert(index, bytes);
+		Ok(())
+	}
+
+	fn open_era(&self, index: u64) -> Result<JournalEra> {
+		let bytes = match self.eras.lock().unwrap().get(&index) {
+			Some(bytes) => bytes.clone(),
+			None => return Err(ErrorKind::JournalEraMissing(index).into()),
+		};
+		let label = PathBuf::from(format!("<in-memory era {}>", index));
+		JournalEra::from_data(index, EraData::Owned(bytes), label)
+	}
+
+	fn list_eras(&self) -> Result<Vec<u64>> {
+		let mut indices: Vec<u64> = self.eras.lock().unwrap().keys().cloned().collect();
+		indices.sort();
+		Ok(indices)
+	}
+
+	fn remove_era(&self, index: u64) -> Result<()> {
+		self.eras.lock().unwrap().remove(&index);
+		Ok(())
+	}
+}
+
+/// Controls what happens to a `Delete` once its era falls out of the retained window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruneMode {
+	/// Never physically remove keys; the journal only ever grows the backing store.
+	Archive,
+	/// Physically remove a key once no retained era still holds a live `Insert` for it.
+	Pruned,
+}
+
+/// Memory usage breakdown for a [`Journal`], summed across all retained eras.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct JournalReport {
+	/// Number of eras currently retained in memory.
+	pub eras: usize,
+	/// Approximate heap footprint of the in-memory operation caches.
+	pub cache_bytes: usize,
+	/// Total size of the mapped/owned era buffers.
+	pub mapped_bytes: usize,
+	/// Approximate heap footprint of the live-insertion-count table used to decide when a
+	/// drained `Delete` is safe to flush.
+	pub live_count_bytes: usize,
+}
+
+/// A transaction that has been durably written to the backend by [`Journal::prepare`] but not
+/// yet installed into the retained era window. Hand it to [`Journal::apply`] to finish the
+/// commit.
+///
+/// If dropped without being applied, the era it wrote is rolled back from the backend so it
+/// can never be resurrected as a "real" retained era on a later [`Journal::open`].
+#[derive(Debug)]
+pub struct PreparedEra {
+	index: u64,
+	backend: Arc<JournalBackend>,
+	applied: bool,
+}
+
+impl Drop for PreparedEra {
+	fn drop(&mut self) {
+		if !self.applied {
+			let _ = self.backend.remove_era(self.index);
+		}
+	}
+}
+
+#[derive(Debug)]
+pub struct Journal {
+	backend: Arc<JournalBackend>,
 	eras: VecDeque<JournalEra>,
-	next_era_index: u64,
+	/// The index that the next `prepare` will reserve. An `AtomicU64` so `prepare` can claim
+	/// a slot through just `&self`, leaving `&mut self` free for the short critical section in
+	/// `apply`.
+	next_era_index: AtomicU64,
+	/// The index `apply` next expects to install. Tracked separately from `next_era_index`
+	/// (which may already have been claimed by an outstanding, not-yet-applied `prepare`) and
+	/// from `eras.back()` (which regresses once the retained window is fully drained).
+	next_apply_index: u64,
+	/// Number of retained eras that currently hold a live `Insert` for a given key.
+	///
+	/// A `Delete` recorded in a drained era is only safe to apply to the backing store
+	/// once this count reaches zero, i.e. no later era re-inserted the key in the meantime.
+	live_counts: HashMap<Vec<u8>, i32>,
+	/// Indices reserved by `prepare` whose `write_era` failed, so the era will never be
+	/// applied. Consulted (and drained) by `apply` so these burned slots are skipped instead
+	/// of permanently wedging `next_apply_index` behind a write that will never arrive.
+	burned_indices: Mutex<HashSet<u64>>,
+	mode: PruneMode,
+	compression: Compression,
 }
 
 impl Journal {
 	pub fn open<P: AsRef<Path>>(jdir: P) -> Result<Self> {
-		let era_files = dir::era_files(&jdir)?;
-		let next_era_index = dir::next_era_index(&era_files)?;
+		Self::open_with_mode(jdir, PruneMode::Pruned)
+	}
 
-		let eras = era_files.into_iter()
-			.map(JournalEra::open)
+	pub fn open_with_mode<P: AsRef<Path>>(jdir: P, mode: PruneMode) -> Result<Self> {
+		Self::open_with_options(jdir, mode, Compression::None)
+	}
+
+	pub fn open_with_options<P: AsRef<Path>>(jdir: P, mode: PruneMode, compression: Compression) -> Result<Self> {
+		migration::migrate_dir(&jdir)?;
+		Self::with_backend(Box::new(MmapJournalBackend::new(jdir)), mode, compression)
+	}
+
+	/// Opens a journal on top of an arbitrary [`JournalBackend`], e.g. an in-memory backend
+	/// for tests and ephemeral DBs.
+	pub fn with_backend(backend: Box<JournalBackend>, mode: PruneMode, compression: Compression) -> Result<Self> {
+		let backend: Arc<JournalBackend> = Arc::from(backend);
+		let era_indices = backend.list_eras()?;
+		let next_era_index = era_indices.last().map(|idx| idx + 1).unwrap_or(0);
+
+		let eras = era_indices.into_iter()
+			.map(|index| backend.open_era(index))
 			.collect::<Result<VecDeque<_>>>()?;
 
-		let journal = Journal {
-			dir: jdir.as_ref().to_path_buf(),
+		let mut journal = Journal {
+			backend,
 			eras,
-			next_era_index,
+			next_era_index: AtomicU64::new(next_era_index),
+			next_apply_index: next_era_index,
+			live_counts: HashMap::new(),
+			burned_indices: Mutex::new(HashSet::new()),
+			mode,
+			compression,
 		};
 
+		for era in &journal.eras {
+			Self::record_inserts(&mut journal.live_counts, era);
+		}
+
 		Ok(journal)
 	}
 
-	pub fn push(&mut self, transaction: &Transaction) -> Result<()> {
-		let new_path = dir::next_era_filename(&self.dir, self.next_era_index);
-		self.next_era_index += 1;
+	/// Does all the expensive work of committing `transaction` — encoding it, reserving the
+	/// next era index, and durably writing it to the backend — while needing only a shared
+	/// reference. The result is installed into the retained era window by a later call to
+	/// [`Journal::apply`], whose critical section is just a push onto `eras`.
+	///
+	/// The index is reserved with a single atomic increment *before* the write is attempted,
+	/// so two concurrent `prepare` calls can never race each other onto the same index (and
+	/// onto the same `write_era` call). If the write then fails, the index is recorded in
+	/// `burned_indices` rather than being retried: it will never be filled, so `apply` skips
+	/// over it instead of getting permanently wedged waiting for it.
+	pub fn prepare(&self, transaction: &Transaction) -> Result<PreparedEra> {
+		let index = self.next_era_index.fetch_add(1, Ordering::SeqCst);
+		if let Err(err) = self.backend.write_era(index, transaction, self.compression) {
+			self.burned_indices.lock().unwrap().insert(index);
+			return Err(err);
+		}
+		Ok(PreparedEra { index, backend: self.backend.clone(), applied: false })
+	}
 
-		let new_era = JournalEra::create(new_path, &transaction)?;
+	/// Maps in the era written by `prepare` and installs it as the newest retained era.
+	///
+	/// Fails if `prepared` is not the next slot this journal expects, which would mean another
+	/// prepared era was applied out of order — e.g. under concurrent `prepare`rs, a later
+	/// era can legitimately finish writing before an earlier one. In that case `prepared` is
+	/// handed straight back to the caller (rather than dropped), so it can be retried with a
+	/// later `apply` call once the era it's actually waiting on has been applied, instead of
+	/// losing the era's data to `PreparedEra`'s `Drop` rollback.
+	pub fn apply(&mut self, mut prepared: PreparedEra) -> ::std::result::Result<(), PreparedEra> {
+		{
+			let mut burned = self.burned_indices.lock().unwrap();
+			while burned.remove(&self.next_apply_index) {
+				self.next_apply_index += 1;
+			}
+		}
+
+		if prepared.index != self.next_apply_index {
+			return Err(prepared);
+		}
+
+		let new_era = match self.backend.open_era(prepared.index) {
+			Ok(era) => era,
+			Err(_) => return Err(prepared),
+		};
+		Self::record_inserts(&mut self.live_counts, &new_era);
 		self.eras.push_back(new_era);
+		self.next_apply_index += 1;
+		prepared.applied = true;
 
 		Ok(())
 	}
 
-	pub fn drain_front(&mut self, elems: usize) -> Drain<JournalEra> {
-		self.eras.drain(..elems)
+	pub fn push(&mut self, transaction: &Transaction) -> Result<()> {
+		let prepared = self.prepare(transaction)?;
+		self.apply(prepared).map_err(|prepared| ErrorKind::CorruptedJournal(
+			PathBuf::new(),
+			format!("prepared era {} is not the expected next slot {}", prepared.index, self.next_apply_index),
+		).into())
+	}
+
+	/// Walks `era`'s operations, bumping the live-insertion count for every `Insert`.
+	/// `Delete`s are left untouched here; they are only acted upon once their era drains.
+	fn record_inserts(live_counts: &mut HashMap<Vec<u8>, i32>, era: &JournalEra) {
+		for (key, op) in &era.cache {
+			if let JournalOperation::Insert(_) = *op {
+				let key = unsafe { key.as_slice() }.to_vec();
+				*live_counts.entry(key).or_insert(0) += 1;
+			}
+		}
+	}
+
+	/// Drops the oldest `elems` eras and returns the consolidated set of operations that
+	/// are now safe to flush to the main DB.
+	///
+	/// For every `Insert` in a drained era the live count for its key is decremented, since
+	/// that era's copy is leaving the retained window. A key's last operation *within this
+	/// drained batch* is tracked separately (`last_ops`), so that e.g. an era draining an
+	/// `Insert` followed (in a later drained era) by a `Delete` of the same key correctly
+	/// yields a `Delete`, not a stale `Insert` — and vice versa.
+	///
+	/// Once every drained era has been accounted for, a key's last operation is only emitted
+	/// if the key's live count has dropped to zero, i.e. no later, still-retained era
+	/// re-inserted it (which would make that era's own eventual drain responsible for
+	/// flushing it instead). An `Insert` is always flushed once it reaches this point —
+	/// otherwise its value would vanish along with the draining era's buffer. A `Delete` is
+	/// only flushed as a physical removal in [`PruneMode::Pruned`].
+	pub fn drain_front(&mut self, elems: usize) -> Result<Vec<Operation>> {
+		let drained: Vec<JournalEra> = self.eras.drain(..elems).collect();
+
+		let mut last_ops: HashMap<Vec<u8>, Operation> = HashMap::new();
+		for era in &drained {
+			for (key, op) in &era.cache {
+				let key = unsafe { key.as_slice() }.to_vec();
+				match *op {
+					JournalOperation::Insert(ref value) => {
+						if let Some(count) = self.live_counts.get_mut(&key) {
+							*count -= 1;
+							if *count <= 0 {
+								self.live_counts.remove(&key);
+							}
+						}
+						let value = unsafe { value.as_slice() }.to_vec();
+						last_ops.insert(key.clone(), Operation::Insert(key, value));
+					},
+					JournalOperation::Delete => {
+						last_ops.insert(key.clone(), Operation::Delete(key));
+					},
+				}
+			}
+		}
+
+		let mut operations = Vec::new();
+		for (key, op) in last_ops {
+			let is_dead = self.live_counts.get(&key).map_or(true, |&count| count <= 0);
+			if !is_dead {
+				continue;
+			}
+			self.live_counts.remove(&key);
+			match op {
+				Operation::Insert(..) => operations.push(op),
+				Operation::Delete(..) => if self.mode == PruneMode::Pruned {
+					operations.push(op);
+				},
+			}
+		}
+
+		for era in drained {
+			self.backend.remove_era(era.index)?;
+		}
+
+		Ok(operations)
 	}
 
 	pub fn len(&self) -> usize {
@@ -266,6 +805,31 @@ impl Journal {
 
 		None
 	}
+
+	/// Approximate heap footprint of the whole journal: the in-memory operation caches plus
+	/// the mapped/owned era buffers, summed across all retained eras, plus the live-insertion
+	/// count table.
+	pub fn mem_used(&self) -> usize {
+		let report = self.report();
+		report.cache_bytes + report.mapped_bytes + report.live_count_bytes
+	}
+
+	/// Memory usage breakdown across all retained eras. Operators running this as a
+	/// long-lived state store can use this to tune how many eras to retain before draining.
+	pub fn report(&self) -> JournalReport {
+		let mut report = JournalReport { eras: self.eras.len(), ..JournalReport::default() };
+
+		for era in &self.eras {
+			report.cache_bytes += era.cache_bytes();
+			report.mapped_bytes += era.mapped_bytes();
+		}
+
+		report.live_count_bytes = self.live_counts.iter()
+			.map(|(key, _)| key.len() + mem::size_of::<(Vec<u8>, i32)>())
+			.sum();
+
+		report
+	}
 }
 
 #[cfg(test)]
@@ -274,16 +838,16 @@ mod tests {
 
 	use self::tempdir::TempDir;
 	use std::fs;
-	use std::io::Write;
+	use std::io::{Seek, SeekFrom, Write};
+	use std::path::Path;
 	use error::ErrorKind;
-	use transaction::Transaction;
-	use super::{Journal, JournalEra, JournalOperation};
+	use transaction::{Transaction, Operation};
+	use super::{Journal, JournalBackend, MmapJournalBackend, InMemoryJournalBackend, JournalOperation, PruneMode, Compression};
 
 	#[test]
 	fn test_era_create() {
 		let temp = TempDir::new("test_era_create").unwrap();
-		let mut path = temp.path().to_path_buf();
-		path.push("file");
+		let backend = MmapJournalBackend::new(temp.path());
 
 		let mut tx = Transaction::default();
 		tx.insert(b"key", b"value");
@@ -292,13 +856,186 @@ mod tests {
 		tx.insert(b"key2", b"value2");
 		tx.delete(b"key3");
 
-		let era = JournalEra::create(path, &tx).unwrap();
+		let era = backend.create_era(0, &tx, Compression::None).unwrap();
 		assert_eq!(JournalOperation::Insert(b"value" as &[u8]), era.get(b"key").unwrap());
 		assert_eq!(JournalOperation::Insert(b"value2" as &[u8]), era.get(b"key2").unwrap());
 		assert_eq!(JournalOperation::Delete, era.get(b"key3").unwrap());
 		assert_eq!(None, era.get(b"key4"));
 	}
 
+	#[test]
+	fn test_era_create_in_memory() {
+		let backend = InMemoryJournalBackend::new();
+
+		let mut tx = Transaction::default();
+		tx.insert(b"key", b"value");
+
+		let era = backend.create_era(0, &tx, Compression::None).unwrap();
+		assert_eq!(JournalOperation::Insert(b"value" as &[u8]), era.get(b"key").unwrap());
+
+		backend.remove_era(0).unwrap();
+		assert!(backend.open_era(0).is_err());
+	}
+
+	#[test]
+	fn test_journal_with_in_memory_backend() {
+		let mut journal = Journal::with_backend(Box::new(InMemoryJournalBackend::new()), PruneMode::Pruned, Compression::None).unwrap();
+
+		journal.push(&Transaction::default()).unwrap();
+		journal.push(&Transaction::default()).unwrap();
+		assert_eq!(journal.len(), 2);
+
+		journal.drain_front(1).unwrap();
+		assert_eq!(journal.len(), 1);
+	}
+
+	#[test]
+	fn test_era_create_with_snappy_compression() {
+		let temp = TempDir::new("test_era_create_with_snappy_compression").unwrap();
+		let backend = MmapJournalBackend::new(temp.path());
+
+		let mut tx = Transaction::default();
+		tx.insert(b"key", b"valuevaluevaluevaluevaluevalue");
+		tx.insert(b"key2", b"valuevaluevaluevaluevaluevalue");
+		tx.delete(b"key3");
+
+		let era = backend.create_era(0, &tx, Compression::Snappy).unwrap();
+		assert_eq!(JournalOperation::Insert(b"valuevaluevaluevaluevaluevalue" as &[u8]), era.get(b"key").unwrap());
+		assert_eq!(JournalOperation::Insert(b"valuevaluevaluevaluevaluevalue" as &[u8]), era.get(b"key2").unwrap());
+		assert_eq!(JournalOperation::Delete, era.get(b"key3").unwrap());
+		assert_eq!(None, era.get(b"key4"));
+
+		// re-opening from disk must decompress transparently and yield the same data.
+		let reopened = backend.open_era(0).unwrap();
+		assert_eq!(JournalOperation::Insert(b"valuevaluevaluevaluevaluevalue" as &[u8]), reopened.get(b"key").unwrap());
+		assert_eq!(JournalOperation::Delete, reopened.get(b"key3").unwrap());
+	}
+
+	#[test]
+	fn journal_push_with_compression_round_trips_through_drain() {
+		let temp = TempDir::new("journal_push_with_compression_round_trips_through_drain").unwrap();
+		let mut journal = Journal::open_with_options(temp.path(), PruneMode::Pruned, Compression::Snappy).unwrap();
+
+		let mut tx = Transaction::default();
+		tx.insert(b"key", b"valuevaluevaluevaluevaluevalue");
+		journal.push(&tx).unwrap();
+		journal.push(&Transaction::default()).unwrap();
+
+		assert_eq!(b"valuevaluevaluevaluevaluevalue" as &[u8], journal.get(b"key").unwrap());
+
+		let ops = journal.drain_front(1).unwrap();
+		assert_eq!(ops, vec![Operation::Insert(b"key".to_vec(), b"valuevaluevaluevaluevaluevalue".to_vec())]);
+	}
+
+	#[test]
+	fn prepare_then_apply_installs_the_era_just_like_push() {
+		let mut journal = Journal::with_backend(Box::new(InMemoryJournalBackend::new()), PruneMode::Pruned, Compression::None).unwrap();
+
+		let mut tx = Transaction::default();
+		tx.insert(b"key", b"value");
+
+		let prepared = journal.prepare(&tx).unwrap();
+		assert_eq!(journal.len(), 0, "apply hasn't run yet, era isn't installed");
+
+		journal.apply(prepared).unwrap();
+		assert_eq!(journal.len(), 1);
+		assert_eq!(b"value" as &[u8], journal.get(b"key").unwrap());
+	}
+
+	#[test]
+	fn apply_rejects_an_era_applied_out_of_its_expected_order_but_hands_it_back_for_a_retry() {
+		let mut journal = Journal::with_backend(Box::new(InMemoryJournalBackend::new()), PruneMode::Pruned, Compression::None).unwrap();
+
+		let first = journal.prepare(&Transaction::default()).unwrap();
+		let second = journal.prepare(&Transaction::default()).unwrap();
+
+		// Rejected for arriving too early, but the caller gets `second` back instead of
+		// losing it: applying `first` and then retrying `second` must still install both.
+		let second = journal.apply(second).unwrap_err();
+		journal.apply(first).unwrap();
+		journal.apply(second).unwrap();
+		assert_eq!(journal.len(), 2);
+	}
+
+	#[test]
+	fn a_prepared_era_dropped_without_being_applied_is_rolled_back_from_disk() {
+		let temp = TempDir::new("a_prepared_era_dropped_without_being_applied_is_rolled_back_from_disk").unwrap();
+		let journal = Journal::open(temp.path()).unwrap();
+
+		{
+			let prepared = journal.prepare(&Transaction::default()).unwrap();
+			assert!(dir_has_era_file(temp.path(), prepared.index));
+		}
+
+		// Dropped without ever being applied: the orphaned era file must not survive, or it
+		// would be silently resurrected as a real retained era on the next `Journal::open`.
+		assert!(!dir_has_era_file(temp.path(), 0));
+		let reopened = Journal::open(temp.path()).unwrap();
+		assert_eq!(reopened.len(), 0);
+	}
+
+	#[test]
+	fn apply_rejecting_a_prepared_era_out_of_order_does_not_roll_it_back_from_disk() {
+		let temp = TempDir::new("apply_rejecting_a_prepared_era_out_of_order_does_not_roll_it_back_from_disk").unwrap();
+		let mut journal = Journal::open(temp.path()).unwrap();
+
+		let first = journal.prepare(&Transaction::default()).unwrap();
+		let second = journal.prepare(&Transaction::default()).unwrap();
+		assert!(dir_has_era_file(temp.path(), second.index));
+
+		// The rejection must not destroy the era: it's still needed once `second` is retried.
+		let second = journal.apply(second).unwrap_err();
+		assert!(dir_has_era_file(temp.path(), 1));
+
+		journal.apply(first).unwrap();
+		journal.apply(second).unwrap();
+		assert_eq!(journal.len(), 2);
+
+		let reopened = Journal::open(temp.path()).unwrap();
+		assert_eq!(reopened.len(), 2);
+	}
+
+	fn dir_has_era_file(dir: &Path, index: u64) -> bool {
+		let mut path = dir.to_path_buf();
+		path.push(format!("{}.era", index));
+		path.exists()
+	}
+
+	#[test]
+	fn apply_skips_over_a_burned_slot_whose_prepare_failed_to_write() {
+		let temp = TempDir::new("apply_skips_over_a_burned_slot_whose_prepare_failed_to_write").unwrap();
+		let mut journal = Journal::open(temp.path()).unwrap();
+
+		// Sabotage era 0 so prepare's write_era call fails after reserving the index, leaving
+		// it burned: it will never be filled, and apply must skip it rather than getting
+		// wedged waiting for a slot 1 can never occupy.
+		fs::File::create(temp.path().join("0.era")).unwrap();
+		assert!(journal.prepare(&Transaction::default()).is_err());
+
+		let mut tx = Transaction::default();
+		tx.insert(b"key", b"value");
+		let prepared = journal.prepare(&tx).unwrap();
+		assert_eq!(prepared.index, 1);
+		journal.apply(prepared).unwrap();
+
+		assert_eq!(journal.len(), 1);
+		assert_eq!(b"value" as &[u8], journal.get(b"key").unwrap());
+	}
+
+	#[test]
+	fn prepare_and_apply_keep_working_after_the_retained_window_fully_drains() {
+		let mut journal = Journal::with_backend(Box::new(InMemoryJournalBackend::new()), PruneMode::Pruned, Compression::None).unwrap();
+
+		journal.push(&Transaction::default()).unwrap();
+		journal.push(&Transaction::default()).unwrap();
+		journal.drain_front(2).unwrap();
+		assert_eq!(journal.len(), 0);
+
+		let prepared = journal.prepare(&Transaction::default()).unwrap();
+		journal.apply(prepared).unwrap();
+		assert_eq!(journal.len(), 1);
+	}
+
 	#[test]
 	fn test_journal_new() {
 		let temp = TempDir::new("test_journal_new").unwrap();
@@ -309,16 +1046,144 @@ mod tests {
 		journal.push(&Transaction::default()).unwrap();
 		assert_eq!(journal.len(), 3);
 
-		journal.drain_front(2);
+		journal.drain_front(2).unwrap();
 
 		assert_eq!(journal.len(), 1);
 	}
 
+	#[test]
+	fn should_not_prune_a_delete_whose_key_was_reinserted_later() {
+		let temp = TempDir::new("should_not_prune_a_delete_whose_key_was_reinserted_later").unwrap();
+		let mut journal = Journal::open_with_mode(temp.path(), PruneMode::Pruned).unwrap();
+
+		let mut first = Transaction::default();
+		first.insert(b"key", b"value");
+		journal.push(&first).unwrap();
+
+		let mut second = Transaction::default();
+		second.delete(b"key");
+		journal.push(&second).unwrap();
+
+		let mut third = Transaction::default();
+		third.insert(b"key", b"value2");
+		journal.push(&third).unwrap();
+
+		// Draining the first two eras must NOT emit a physical removal: the key is
+		// still alive thanks to the `Insert` in the third, still-retained, era.
+		let ops = journal.drain_front(2).unwrap();
+		assert_eq!(ops, Vec::new());
+	}
+
+	#[test]
+	fn should_prune_a_delete_once_its_key_has_no_live_inserts_left() {
+		let temp = TempDir::new("should_prune_a_delete_once_its_key_has_no_live_inserts_left").unwrap();
+		let mut journal = Journal::open_with_mode(temp.path(), PruneMode::Pruned).unwrap();
+
+		let mut first = Transaction::default();
+		first.insert(b"key", b"value");
+		journal.push(&first).unwrap();
+
+		let mut second = Transaction::default();
+		second.delete(b"key");
+		journal.push(&second).unwrap();
+
+		let ops = journal.drain_front(2).unwrap();
+		assert_eq!(ops, vec![Operation::Delete(b"key".to_vec())]);
+	}
+
+	#[test]
+	fn archive_mode_never_emits_removals() {
+		let temp = TempDir::new("archive_mode_never_emits_removals").unwrap();
+		let mut journal = Journal::open_with_mode(temp.path(), PruneMode::Archive).unwrap();
+
+		let mut tx = Transaction::default();
+		tx.insert(b"key", b"value");
+		journal.push(&tx).unwrap();
+
+		let mut tx = Transaction::default();
+		tx.delete(b"key");
+		journal.push(&tx).unwrap();
+
+		let ops = journal.drain_front(2).unwrap();
+		assert_eq!(ops, Vec::new());
+	}
+
+	#[test]
+	fn report_tracks_retained_eras_and_buffer_sizes() {
+		let mut journal = Journal::with_backend(Box::new(InMemoryJournalBackend::new()), PruneMode::Pruned, Compression::None).unwrap();
+		assert_eq!(journal.report(), super::JournalReport::default());
+
+		let mut tx = Transaction::default();
+		tx.insert(b"key", b"value");
+		journal.push(&tx).unwrap();
+
+		let report = journal.report();
+		assert_eq!(report.eras, 1);
+		assert!(report.mapped_bytes > 0);
+		assert!(report.live_count_bytes > 0);
+		assert_eq!(journal.mem_used(), report.cache_bytes + report.mapped_bytes + report.live_count_bytes);
+	}
+
+	#[test]
+	fn should_emit_a_delete_only_once_even_if_two_drained_eras_delete_the_same_key() {
+		let mut journal = Journal::with_backend(Box::new(InMemoryJournalBackend::new()), PruneMode::Pruned, Compression::None).unwrap();
+
+		let mut tx = Transaction::default();
+		tx.insert(b"key", b"value");
+		journal.push(&tx).unwrap();
+
+		let mut tx = Transaction::default();
+		tx.delete(b"key");
+		journal.push(&tx).unwrap();
+
+		let mut tx = Transaction::default();
+		tx.delete(b"key");
+		journal.push(&tx).unwrap();
+
+		let ops = journal.drain_front(3).unwrap();
+		assert_eq!(ops, vec![Operation::Delete(b"key".to_vec())]);
+	}
+
+	#[test]
+	fn a_reinsert_after_a_delete_in_the_same_drained_batch_wins_over_the_delete() {
+		let mut journal = Journal::with_backend(Box::new(InMemoryJournalBackend::new()), PruneMode::Pruned, Compression::None).unwrap();
+
+		let mut tx = Transaction::default();
+		tx.insert(b"key", b"value");
+		journal.push(&tx).unwrap();
+
+		let mut tx = Transaction::default();
+		tx.delete(b"key");
+		journal.push(&tx).unwrap();
+
+		let mut tx = Transaction::default();
+		tx.insert(b"key", b"value2");
+		journal.push(&tx).unwrap();
+
+		// All three eras drain together: the key's net effect within the batch is the final
+		// `Insert`, so that must be what's flushed, not a stale `Delete` from the middle era.
+		let ops = journal.drain_front(3).unwrap();
+		assert_eq!(ops, vec![Operation::Insert(b"key".to_vec(), b"value2".to_vec())]);
+	}
+
+	#[test]
+	fn live_counts_do_not_leak_memory_for_insert_only_keys_once_drained() {
+		let mut journal = Journal::with_backend(Box::new(InMemoryJournalBackend::new()), PruneMode::Pruned, Compression::None).unwrap();
+
+		let mut tx = Transaction::default();
+		tx.insert(b"key", b"value");
+		journal.push(&tx).unwrap();
+
+		assert!(journal.report().live_count_bytes > 0);
+
+		journal.drain_front(1).unwrap();
+		assert_eq!(journal.report().live_count_bytes, 0);
+	}
+
 	#[test]
 	fn should_detect_corrupted_era() {
 		let temp = TempDir::new("test_era_create").unwrap();
-		let mut path = temp.path().to_path_buf();
-		path.push("file");
+		let backend = MmapJournalBackend::new(temp.path());
 
 		let mut tx = Transaction::default();
 		tx.insert(b"key", b"value");
@@ -326,17 +1191,47 @@ mod tests {
 		tx.insert(b"key3", b"value");
 		tx.insert(b"key2", b"value2");
 		tx.delete(b"key3");
-		let _ = JournalEra::create(&path, &tx).unwrap();
+		let _ = backend.create_era(0, &tx, Compression::None).unwrap();
 
-		// alter hash
+		let mut path = temp.path().to_path_buf();
+		path.push("0.era");
+
+		// alter hash (stored right after the era header)
 		let mut file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+		file.seek(SeekFrom::Start(super::HEADER_SIZE as u64)).unwrap();
 		file.write_all(&mut [1, 2, 3]).unwrap();
 		file.flush().unwrap();
 
 		// Try to open era
-		assert_eq!(JournalEra::open(&path).unwrap_err().kind(), &ErrorKind::CorruptedJournal(
+		assert_eq!(backend.open_era(0).unwrap_err().kind(), &ErrorKind::CorruptedJournal(
 			path,
 			"Expected: [56 63 c1 ca 5a 6d 4e d2 b1 e9 70 87 64 79 c2 7c 67 42 44 52 52 37 78 c5 6b 7a 8a 89 e5 de f1 3a], Got: [1 2 3 ca 5a 6d 4e d2 b1 e9 70 87 64 79 c2 7c 67 42 44 52 52 37 78 c5 6b 7a 8a 89 e5 de f1 3a]".into()
 		));
 	}
+
+	#[test]
+	fn should_transparently_migrate_a_corpus_of_old_format_eras() {
+		let temp = TempDir::new("should_transparently_migrate_a_corpus_of_old_format_eras").unwrap();
+
+		// Hand-write a couple of eras in the pre-header (v0) format: a bare sha3 checksum
+		// followed by the raw transaction bytes, no magic/version/flags.
+		for (index, value) in [(0u64, b"old era one".to_vec()), (1u64, b"old era two".to_vec())].iter().cloned() {
+			let mut tx = Transaction::default();
+			tx.insert(b"key", &value);
+
+			let hash = super::sha3_256(tx.raw());
+			let mut path = temp.path().to_path_buf();
+			path.push(format!("{}.era", index));
+
+			let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&path).unwrap();
+			file.write_all(&hash).unwrap();
+			file.write_all(tx.raw()).unwrap();
+			file.flush().unwrap();
+		}
+
+		// Opening the journal transparently migrates every era to the current format.
+		let journal = Journal::open(temp.path()).unwrap();
+		assert_eq!(journal.len(), 2);
+		assert_eq!(journal.get(b"key"), Some(b"old era two" as &[u8]));
+	}
 }